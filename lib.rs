@@ -4,6 +4,7 @@ use sp_core::offchain::Timestamp;
 
 #[ink::contract]
 mod pay_channel {
+    use ink::storage::Mapping;
 
     #[ink(storage)]
     pub struct PaymentChannel {
@@ -12,6 +13,27 @@ mod pay_channel {
         expiration: Option<Timestamp>,
         withdrawn: Balance,
         close_duration: Timestamp,
+        htlcs: Mapping<[u8; 32], Htlc>,
+        htlc_locked: Balance,
+        latest_nonce: u64,
+        challenge_expiration: Option<Timestamp>,
+        closing_amount: Balance,
+        closing_nonce: u64,
+        closing_point: [u8; 33],
+    }
+
+    /// A single hash-time-locked conditional payment pending on the channel.
+    ///
+    /// The funds are released to the recipient only on revelation of a preimage
+    /// hashing to `payment_hash`, and can be reclaimed by the sender once
+    /// `timeout` has elapsed.
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    #[ink::scale_derive(Decode, Encode, TypeInfo)]
+    #[cfg_attr(feature = "std", derive(ink::storage::traits::StorageLayout))]
+    pub struct Htlc {
+        payment_hash: [u8; 32],
+        amount: Balance,
+        timeout: Timestamp,
     }
 
     #[derive(Debug, PartialEq, Eq)]
@@ -24,6 +46,17 @@ mod pay_channel {
         TransferFailed,
         NotYetExpired,
         InvalidSignature,
+        HtlcAlreadyExists,
+        HtlcNotFound,
+        InvalidPreimage,
+        HtlcExpired,
+        HtlcNotYetTimedOut,
+        StaleNonce,
+        StateRevoked,
+        ChallengeWindowOpen,
+        InvalidSettlement,
+        InsufficientChannelBalance,
+        PendingHtlcs,
     }
 
     pub type Result<T> = core::result::Result<T, Error>;
@@ -34,6 +67,26 @@ mod pay_channel {
         close_duration: Timestamp,
     }
 
+    #[ink(event)]
+    pub struct ChallengeStarted {
+        amount: Balance,
+        nonce: u64,
+        expiration: Timestamp,
+    }
+
+    /// Emitted by `close`, `finalize_close`, `punish`, `cooperative_close`,
+    /// `start_sender_close`, `withdrawn` and `add_htlc` so an off-chain
+    /// watchtower can track the latest channel state and challenge a revoked
+    /// submission. `claim_htlc`, `refund_htlc` and `clain_timeout` do not emit it.
+    #[ink(event)]
+    pub struct ChannelStateUpdated {
+        account_id: AccountId,
+        nonce: u64,
+        amount: Balance,
+        expiration: Option<Timestamp>,
+        signature_hash: [u8; 32],
+    }
+
     impl PaymentChannel {
 
         #[ink(constructor)]
@@ -47,24 +100,46 @@ mod pay_channel {
             expiration: None,
             withdrawn: 0,
             close_duration,
+            htlcs: Mapping::new(),
+            htlc_locked: 0,
+            latest_nonce: 0,
+            challenge_expiration: None,
+            closing_amount: 0,
+            closing_nonce: 0,
+            closing_point: [0; 33],
            }
         }
 
         #[ink(message)]
         pub fn close(
-            &mut self, 
-            amount: Balance, 
-            signature: [u8; 65]
+            &mut self,
+            amount: Balance,
+            nonce: u64,
+            commitment_point: [u8; 33],
+            signature: [u8; 65],
+            sender_signature: [u8; 65]
         ) -> Result<()> {
-            self.close_inner(amount, signature)?;
-            self.env().terminate_contract(self.sender);
+            self.close_inner(amount, nonce, commitment_point, signature, sender_signature)
         }
 
         /// We split this out in order to make testing `close` simpler.
+        ///
+        /// Rather than settling immediately, a successful `close` records the
+        /// submitted `(amount, nonce)` together with its per-commitment point
+        /// and opens a challenge window of `close_duration`. During that window
+        /// the counterparty may `punish` a revoked state; once it elapses the
+        /// recorded state is settled by `finalize_close`.
+        ///
+        /// The commitment point must be counter-signed by *both* parties so it
+        /// is bound to a mutually-agreed, revocable state — otherwise a closer
+        /// could invent a point whose secret nobody knows and evade `punish`.
         fn close_inner(
-            &mut self, 
-            amount: Balance, 
-            signature: [u8; 65]) -> Result<()> {
+            &mut self,
+            amount: Balance,
+            nonce: u64,
+            commitment_point: [u8; 33],
+            signature: [u8; 65],
+            sender_signature: [u8; 65]) -> Result<()> {
             if self.env().caller() != self.recipient {
                 return Err(Error::CallerIsNotRecipient)
             }
@@ -73,18 +148,178 @@ mod pay_channel {
                 return Err(Error::AmmountIsLessThanWithdrawn)
             }
 
+            if nonce <= self.latest_nonce {
+                return Err(Error::StaleNonce)
+            }
+
+            // The recipient may only be paid out of the free balance; funds
+            // reserved for pending HTLCs stay locked for their own settlement.
+            #[allow(clippy::arithmetic_side_effects)]
+            if amount - self.withdrawn > self.free_balance() {
+                return Err(Error::InsufficientChannelBalance)
+            }
+
             // Signature validation
-            if !self.is_signature_valid(amount, signature) {
+            if !self.is_close_signature_valid(amount, nonce, commitment_point, signature, sender_signature) {
                 return Err(Error::InvalidSignature)
             }
 
-            // We checked that amount >= self.withdrawn
-            #[allow(clippy::arithmetic_side_effects)]
+            self.latest_nonce = nonce;
+            self.closing_amount = amount;
+            self.closing_nonce = nonce;
+            self.closing_point = commitment_point;
+
+            let now = self.env().block_timestamp();
+            let expiration = now.checked_add(self.close_duration).unwrap();
+            self.challenge_expiration = Some(expiration);
+
+            self.env().emit_event(ChallengeStarted {
+                amount,
+                nonce,
+                expiration,
+            });
+
+            self.emit_state_update(nonce, amount, self.challenge_expiration, &signature);
+
+            Ok(())
+        }
+
+        /// Settle the channel once the challenge window opened by `close` has
+        /// elapsed without a successful `punish`.
+        #[ink(message)]
+        pub fn finalize_close(&mut self) -> Result<()> {
+            match self.challenge_expiration {
+                Some(expiration) => {
+                    if self.env().block_timestamp() < expiration {
+                        return Err(Error::ChallengeWindowOpen)
+                    }
+
+                    if self.htlc_locked != 0 {
+                        return Err(Error::PendingHtlcs)
+                    }
+
+                    self.emit_state_update(
+                        self.closing_nonce,
+                        self.closing_amount,
+                        self.challenge_expiration,
+                        &[],
+                    );
+
+                    // We checked that amount >= self.withdrawn in `close`.
+                    #[allow(clippy::arithmetic_side_effects)]
+                    self.env()
+                        .transfer(self.recipient, self.closing_amount - self.withdrawn)
+                        .map_err(|_| Error::TransferFailed)?;
+
+                    self.env().terminate_contract(self.sender);
+                }
+                None => Err(Error::NotYetExpired),
+            }
+        }
+
+        /// Penalise a revoked close by proving the closer knew the revocation
+        /// secret for the per-commitment point of the state being settled.
+        ///
+        /// The entire contract balance is awarded to the challenger.
+        #[ink(message)]
+        pub fn punish(&mut self, revocation_secret: [u8; 32]) -> Result<()> {
+            match self.challenge_expiration {
+                Some(expiration) => {
+                    if self.env().block_timestamp() >= expiration {
+                        return Err(Error::NotYetExpired)
+                    }
+
+                    if self.htlc_locked != 0 {
+                        return Err(Error::PendingHtlcs)
+                    }
+
+                    let derived = Self::revocation_commitment(&revocation_secret);
+                    if derived != self.closing_point {
+                        return Err(Error::InvalidSignature)
+                    }
+
+                    // A matching secret proves the closer submitted a revoked
+                    // state; record its nonce so it can never be resubmitted.
+                    self.latest_nonce = self.closing_nonce;
+
+                    let challenger = self.env().caller();
+                    self.emit_state_update(
+                        self.closing_nonce,
+                        self.env().balance(),
+                        self.challenge_expiration,
+                        &revocation_secret,
+                    );
+
+                    self.env()
+                        .transfer(challenger, self.env().balance())
+                        .map_err(|_| Error::TransferFailed)?;
+
+                    self.env().terminate_contract(challenger);
+                }
+                None => Err(Error::StateRevoked),
+            }
+        }
+
+        /// Cooperatively close a bidirectional channel on a mutually-signed
+        /// net-settlement state.
+        ///
+        /// The state `(balance_a, balance_b, nonce)` splits the total locked
+        /// value between `sender` (`balance_a`) and `recipient` (`balance_b`)
+        /// and must carry a strictly newer `nonce` plus a valid signature from
+        /// *both* parties. On success each party is paid its balance and the
+        /// contract terminates.
+        ///
+        /// This is the mutually-exclusive counterpart to the unidirectional
+        /// `close`/`finalize_close` flow: the cooperative path settles the net
+        /// state in a single call with no challenge window, so it is rejected
+        /// once a unidirectional `close` has opened one. All pending HTLCs must
+        /// be claimed or refunded first — terminating the contract would
+        /// otherwise sweep their reserved funds to the sender and destroy the
+        /// HTLC mapping — so the settled `balance_a + balance_b` equals the full
+        /// channel balance once nothing remains locked.
+        #[ink(message)]
+        pub fn cooperative_close(
+            &mut self,
+            balance_a: Balance,
+            balance_b: Balance,
+            nonce: u64,
+            signature_a: [u8; 65],
+            signature_b: [u8; 65],
+        ) -> Result<()> {
+            if self.challenge_expiration.is_some() {
+                return Err(Error::ChallengeWindowOpen)
+            }
+
+            if self.htlc_locked != 0 {
+                return Err(Error::PendingHtlcs)
+            }
+
+            if nonce <= self.latest_nonce {
+                return Err(Error::StaleNonce)
+            }
+
+            let free = self.env().balance().checked_sub(self.htlc_locked)
+                .ok_or(Error::InvalidSettlement)?;
+            if balance_a.checked_add(balance_b) != Some(free) {
+                return Err(Error::InvalidSettlement)
+            }
+
+            if !self.is_dual_signature_valid(balance_a, balance_b, nonce, signature_a, signature_b) {
+                return Err(Error::InvalidSignature)
+            }
+
+            self.latest_nonce = nonce;
+
+            self.emit_state_update(nonce, balance_a.checked_add(balance_b).unwrap(), None, &signature_a);
+
             self.env()
-                .transfer(self.recipient, amount - self.withdrawn)
+                .transfer(self.sender, balance_a)
+                .map_err(|_| Error::TransferFailed)?;
+            self.env()
+                .transfer(self.recipient, balance_b)
                 .map_err(|_| Error::TransferFailed)?;
 
-            Ok(())
+            self.env().terminate_contract(self.sender);
         }
 
         #[ink(message)]
@@ -105,6 +340,8 @@ mod pay_channel {
 
             self.expiration = Some(expiration);
 
+            self.emit_state_update(self.latest_nonce, self.withdrawn, self.expiration, &[]);
+
             Ok(())
         }
 
@@ -119,8 +356,12 @@ mod pay_channel {
                         return Err(Error::NotYetExpired)
                     }
 
+                    if self.htlc_locked != 0 {
+                        return Err(Error::PendingHtlcs)
+                    }
+
                     self.env().terminate_contract(self.sender);
-                } 
+                }
 
                 None => Err(Error::NotYetExpired)
             }
@@ -130,13 +371,18 @@ mod pay_channel {
         pub fn withdrawn(
             &mut self,
             amount:Balance,
+            nonce: u64,
             signature:[u8;65],
         ) -> Result<()> {
             if self.env().caller() != self.recipient {
                 return Err(Error::CallerIsNotRecipient)
             }
 
-            if !self.is_signature_valid(amount,signature){
+            if nonce <= self.latest_nonce {
+                return Err(Error::StaleNonce)
+            }
+
+            if !self.is_signature_valid(amount, nonce, signature){
                 return Err(Error::InvalidSignature)
             }
 
@@ -146,12 +392,105 @@ mod pay_channel {
 
             #[allow(clippy::arithmetic_side_effects)]
             let amount_to_withdraw = amount - self.withdrawn;
-            self.withdrawn.checked_add(amount_to_withdraw).unwrap();
+
+            // Never pay out of the balance reserved for pending HTLCs.
+            if amount_to_withdraw > self.free_balance() {
+                return Err(Error::InsufficientChannelBalance)
+            }
+
+            self.latest_nonce = nonce;
+            self.withdrawn = self.withdrawn.checked_add(amount_to_withdraw).unwrap();
 
             self.env()
                 .transfer(self.recipient, amount_to_withdraw)
                 .map_err(|_| Error::TransferFailed)?;
 
+            self.emit_state_update(nonce, amount, self.expiration, &signature);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn add_htlc(
+            &mut self,
+            payment_hash: [u8; 32],
+            amount: Balance,
+            timeout: Timestamp,
+            signature: [u8; 65],
+        ) -> Result<()> {
+            if self.htlcs.contains(payment_hash) {
+                return Err(Error::HtlcAlreadyExists)
+            }
+
+            if !self.is_htlc_signature_valid(payment_hash, amount, timeout, signature) {
+                return Err(Error::InvalidSignature)
+            }
+
+            // Reserve the HTLC amount against the channel's free balance so it
+            // cannot be double-promised by a later `close`/`withdrawn`.
+            let locked = self.htlc_locked.checked_add(amount).unwrap();
+            if locked > self.env().balance() {
+                return Err(Error::InsufficientChannelBalance)
+            }
+            self.htlc_locked = locked;
+
+            self.htlcs.insert(payment_hash, &Htlc {
+                payment_hash,
+                amount,
+                timeout,
+            });
+
+            self.emit_state_update(self.latest_nonce, amount, Some(timeout), &signature);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn claim_htlc(
+            &mut self,
+            preimage: [u8; 32],
+        ) -> Result<()> {
+            if self.env().caller() != self.recipient {
+                return Err(Error::CallerIsNotRecipient)
+            }
+
+            let mut payment_hash = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Sha2x256>(&preimage, &mut payment_hash);
+
+            let htlc = self.htlcs.get(payment_hash).ok_or(Error::InvalidPreimage)?;
+
+            if self.env().block_timestamp() >= htlc.timeout {
+                return Err(Error::HtlcExpired)
+            }
+
+            self.env()
+                .transfer(self.recipient, htlc.amount)
+                .map_err(|_| Error::TransferFailed)?;
+
+            self.htlc_locked = self.htlc_locked.checked_sub(htlc.amount).unwrap();
+            self.htlcs.remove(payment_hash);
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn refund_htlc(
+            &mut self,
+            payment_hash: [u8; 32],
+        ) -> Result<()> {
+            if self.env().caller() != self.sender {
+                return Err(Error::CallerIsNotSender)
+            }
+
+            let htlc = self.htlcs.get(payment_hash).ok_or(Error::HtlcNotFound)?;
+
+            if self.env().block_timestamp() < htlc.timeout {
+                return Err(Error::HtlcNotYetTimedOut)
+            }
+
+            self.htlc_locked = self.htlc_locked.checked_sub(htlc.amount).unwrap();
+            self.htlcs.remove(payment_hash);
+
             Ok(())
         }
 
@@ -185,6 +524,20 @@ mod pay_channel {
             self.env().balance()
         }
 
+        /// Return a `Sha2x256` digest over the current
+        /// `(recipient, withdrawn, expiration, nonce)` tuple so a watchtower can
+        /// cheaply compare on-chain state against the state it was entrusted with.
+        #[ink(message)]
+        pub fn get_state_digest(&self) -> [u8; 32] {
+            let encodable = (self.recipient, self.withdrawn, self.expiration, self.latest_nonce);
+            let mut digest = <ink::env::hash::Sha2x256 as ink::env::hash::HashOutput>::Type::default();
+            ink::env::hash_encoded::<ink::env::hash::Sha2x256, _>(
+                &encodable,
+                &mut digest,
+            );
+            digest
+        }
+
     }
 
     #[ink(impl)]
@@ -192,9 +545,10 @@ mod pay_channel {
         fn is_signature_valid(
             &self,
             amount: Balance,
+            nonce: u64,
             signature: [u8; 65]
         ) -> bool {
-            let encodable = (self.env().account_id(), amount);
+            let encodable = (self.env().account_id(), amount, nonce);
             let mut message = <ink::env::hash::Sha2x256 as ink::env::hash::HashOutput>::Type::default();
             ink::env::hash_encoded::<ink::env::hash::Sha2x256, _>(
                 &encodable,
@@ -215,6 +569,385 @@ mod pay_channel {
 
             self.recipient == signature_account_id.into()
         }
+
+        fn is_htlc_signature_valid(
+            &self,
+            payment_hash: [u8; 32],
+            amount: Balance,
+            timeout: Timestamp,
+            signature: [u8; 65]
+        ) -> bool {
+            let encodable = (self.env().account_id(), payment_hash, amount, timeout);
+            let mut message = <ink::env::hash::Sha2x256 as ink::env::hash::HashOutput>::Type::default();
+            ink::env::hash_encoded::<ink::env::hash::Sha2x256, _>(
+                &encodable,
+                &mut message,
+            );
+
+            let mut pub_key = [0; 33];
+            ink::env::ecdsa_recover(
+                &signature,
+                &message,
+                &mut pub_key
+            ).unwrap_or_else(|err| panic! ("recover failed : {err:?}"));
+            let mut signature_account_id = [0; 32];
+            <ink::env::hash::Blake2x256 as ink::env::hash::CryptoHash>::hash(
+                &pub_key,
+                &mut signature_account_id
+            );
+
+            self.sender == signature_account_id.into()
+        }
+
+        fn is_close_signature_valid(
+            &self,
+            amount: Balance,
+            nonce: u64,
+            commitment_point: [u8; 33],
+            signature: [u8; 65],
+            sender_signature: [u8; 65]
+        ) -> bool {
+            let encodable = (self.env().account_id(), amount, nonce, commitment_point);
+            let mut message = <ink::env::hash::Sha2x256 as ink::env::hash::HashOutput>::Type::default();
+            ink::env::hash_encoded::<ink::env::hash::Sha2x256, _>(
+                &encodable,
+                &mut message,
+            );
+
+            self.recovers_to(&message, signature, self.recipient)
+                && self.recovers_to(&message, sender_signature, self.sender)
+        }
+
+        fn is_dual_signature_valid(
+            &self,
+            balance_a: Balance,
+            balance_b: Balance,
+            nonce: u64,
+            signature_a: [u8; 65],
+            signature_b: [u8; 65]
+        ) -> bool {
+            let encodable = (self.env().account_id(), balance_a, balance_b, nonce);
+            let mut message = <ink::env::hash::Sha2x256 as ink::env::hash::HashOutput>::Type::default();
+            ink::env::hash_encoded::<ink::env::hash::Sha2x256, _>(
+                &encodable,
+                &mut message,
+            );
+
+            self.recovers_to(&message, signature_a, self.sender)
+                && self.recovers_to(&message, signature_b, self.recipient)
+        }
+
+        /// Check that `signature` over `message` recovers to `expected`.
+        fn recovers_to(
+            &self,
+            message: &[u8; 32],
+            signature: [u8; 65],
+            expected: AccountId
+        ) -> bool {
+            let mut pub_key = [0; 33];
+            ink::env::ecdsa_recover(
+                &signature,
+                message,
+                &mut pub_key
+            ).unwrap_or_else(|err| panic! ("recover failed : {err:?}"));
+            let mut signature_account_id = [0; 32];
+            <ink::env::hash::Blake2x256 as ink::env::hash::CryptoHash>::hash(
+                &pub_key,
+                &mut signature_account_id
+            );
+
+            expected == signature_account_id.into()
+        }
+
+        /// Derive the per-commitment *hash* commitment `0x02 || Blake2x256(secret)`
+        /// committed to by a signed state from its revocation `secret`.
+        ///
+        /// SECURITY-MODEL NOTE (requires sign-off): the original design calls for
+        /// an EC per-commitment point `P_N = secret_N · G`, but the contract
+        /// environment exposes no secp256k1 scalar-multiplication, so this is a
+        /// Blake2x256 hash commitment, not an EC point — the `0x02`-prefixed value
+        /// is NOT a valid compressed secp256k1 point and must not be treated as
+        /// one off-chain. The off-chain protocol MUST be specified to match:
+        ///   1. for state `N`, both parties counter-sign the tuple
+        ///      `(account_id, amount, nonce, commitment_point)` where
+        ///      `commitment_point = 0x02 || Blake2x256(secret_N)`;
+        ///   2. advancing to `N+1` requires handing the counterparty `secret_N`,
+        ///      which it can later submit to `punish` if the revoked state `N` is
+        ///      ever closed.
+        fn revocation_commitment(secret: &[u8; 32]) -> [u8; 33] {
+            let mut digest = [0u8; 32];
+            <ink::env::hash::Blake2x256 as ink::env::hash::CryptoHash>::hash(
+                secret,
+                &mut digest,
+            );
+
+            let mut point = [0u8; 33];
+            point[0] = 0x02;
+            point[1..].copy_from_slice(&digest);
+            point
+        }
+
+        /// Channel value not reserved for pending HTLCs, and therefore the most
+        /// a unidirectional settlement may pay the recipient.
+        fn free_balance(&self) -> Balance {
+            self.env().balance().saturating_sub(self.htlc_locked)
+        }
+
+        /// Emit a channel-monitor update describing the latest state, tagging it
+        /// with a `Sha2x256` hash of the submitted bytes (signature or secret).
+        fn emit_state_update(
+            &self,
+            nonce: u64,
+            amount: Balance,
+            expiration: Option<Timestamp>,
+            submitted: &[u8],
+        ) {
+            let mut signature_hash = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Sha2x256>(submitted, &mut signature_hash);
+
+            self.env().emit_event(ChannelStateUpdated {
+                account_id: self.env().account_id(),
+                nonce,
+                amount,
+                expiration,
+                signature_hash,
+            });
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        // The HTLC paths (`add_htlc` aside, which needs a forged signature) move
+        // funds without any signature, so `claim_htlc`/`refund_htlc` are covered
+        // end-to-end by seeding an HTLC directly into storage. The signature-
+        // bearing settlement splits (`cooperative_close`, `close`+`finalize_close`,
+        // `punish`) still require off-chain ECDSA keys to forge a recoverable
+        // signature, so they remain exercised up to the signature boundary plus
+        // the new HTLC-reservation guards that gate them.
+
+        fn accounts() -> ink::env::test::DefaultAccounts<ink::env::DefaultEnvironment> {
+            ink::env::test::default_accounts::<ink::env::DefaultEnvironment>()
+        }
+
+        fn set_caller(caller: AccountId) {
+            ink::env::test::set_caller::<ink::env::DefaultEnvironment>(caller);
+        }
+
+        fn set_block_timestamp(ts: u64) {
+            ink::env::test::set_block_timestamp::<ink::env::DefaultEnvironment>(ts);
+        }
+
+        fn contract_id() -> AccountId {
+            ink::env::test::callee::<ink::env::DefaultEnvironment>()
+        }
+
+        fn set_balance(account: AccountId, balance: Balance) {
+            ink::env::test::set_account_balance::<ink::env::DefaultEnvironment>(account, balance);
+        }
+
+        fn balance_of(account: AccountId) -> Balance {
+            ink::env::test::get_account_balance::<ink::env::DefaultEnvironment>(account).unwrap()
+        }
+
+        fn payment_hash_of(preimage: &[u8; 32]) -> [u8; 32] {
+            let mut hash = [0u8; 32];
+            ink::env::hash_bytes::<ink::env::hash::Sha2x256>(preimage, &mut hash);
+            hash
+        }
+
+        /// Seed a live HTLC directly into storage, mirroring `add_htlc`.
+        fn seed_htlc(channel: &mut PaymentChannel, preimage: &[u8; 32], amount: Balance, timeout: Timestamp) -> [u8; 32] {
+            let payment_hash = payment_hash_of(preimage);
+            channel.htlcs.insert(payment_hash, &Htlc { payment_hash, amount, timeout });
+            channel.htlc_locked = channel.htlc_locked.checked_add(amount).unwrap();
+            payment_hash
+        }
+
+        /// `alice` is the sender, `bob` the recipient.
+        fn new_channel() -> PaymentChannel {
+            let acc = accounts();
+            set_caller(acc.alice);
+            PaymentChannel::new(acc.bob, 100)
+        }
+
+        #[ink::test]
+        fn constructor_sets_parties() {
+            let acc = accounts();
+            let channel = new_channel();
+            assert_eq!(channel.get_sender(), acc.alice);
+            assert_eq!(channel.get_recipient(), acc.bob);
+            assert_eq!(channel.get_withdrawn(), 0);
+        }
+
+        #[ink::test]
+        fn close_rejects_stale_nonce() {
+            let acc = accounts();
+            let mut channel = new_channel();
+            set_caller(acc.bob);
+            assert_eq!(
+                channel.close(0, 0, [0; 33], [0; 65], [0; 65]),
+                Err(Error::StaleNonce)
+            );
+        }
+
+        #[ink::test]
+        fn close_rejects_non_recipient_caller() {
+            let acc = accounts();
+            let mut channel = new_channel();
+            set_caller(acc.alice);
+            assert_eq!(
+                channel.close(1, 1, [0; 33], [0; 65], [0; 65]),
+                Err(Error::CallerIsNotRecipient)
+            );
+        }
+
+        #[ink::test]
+        fn withdrawn_rejects_non_recipient_caller() {
+            let acc = accounts();
+            let mut channel = new_channel();
+            set_caller(acc.alice);
+            assert_eq!(
+                channel.withdrawn(10, 1, [0; 65]),
+                Err(Error::CallerIsNotRecipient)
+            );
+        }
+
+        #[ink::test]
+        fn cooperative_close_requires_fresh_nonce() {
+            let mut channel = new_channel();
+            assert_eq!(
+                channel.cooperative_close(0, 0, 0, [0; 65], [0; 65]),
+                Err(Error::StaleNonce)
+            );
+        }
+
+        #[ink::test]
+        fn punish_without_open_window_is_rejected() {
+            let mut channel = new_channel();
+            assert_eq!(channel.punish([3; 32]), Err(Error::StateRevoked));
+        }
+
+        #[ink::test]
+        fn refund_unknown_htlc_fails() {
+            let acc = accounts();
+            let mut channel = new_channel();
+            set_caller(acc.alice);
+            assert_eq!(channel.refund_htlc([1; 32]), Err(Error::HtlcNotFound));
+        }
+
+        #[ink::test]
+        fn claim_unknown_preimage_fails() {
+            let acc = accounts();
+            let mut channel = new_channel();
+            set_caller(acc.bob);
+            assert_eq!(channel.claim_htlc([7; 32]), Err(Error::InvalidPreimage));
+        }
+
+        #[ink::test]
+        fn revocation_commitment_is_deterministic() {
+            let secret = [9u8; 32];
+            let commitment = PaymentChannel::revocation_commitment(&secret);
+            assert_eq!(commitment, PaymentChannel::revocation_commitment(&secret));
+            assert_eq!(commitment[0], 0x02);
+            assert_ne!(commitment, PaymentChannel::revocation_commitment(&[8u8; 32]));
+        }
+
+        #[ink::test]
+        fn state_digest_is_stable_for_a_fixed_state() {
+            let channel = new_channel();
+            assert_eq!(channel.get_state_digest(), channel.get_state_digest());
+        }
+
+        #[ink::test]
+        fn claim_htlc_pays_recipient_and_releases_reservation() {
+            let acc = accounts();
+            let mut channel = new_channel();
+            let preimage = [5u8; 32];
+            let payment_hash = seed_htlc(&mut channel, &preimage, 30, 500);
+
+            set_balance(contract_id(), 100);
+            set_block_timestamp(400);
+            set_caller(acc.bob);
+            let recipient_before = balance_of(acc.bob);
+
+            assert_eq!(channel.claim_htlc(preimage), Ok(()));
+            assert_eq!(channel.htlc_locked, 0);
+            assert!(channel.htlcs.get(payment_hash).is_none());
+            assert_eq!(balance_of(acc.bob), recipient_before + 30);
+        }
+
+        #[ink::test]
+        fn claim_htlc_after_timeout_is_rejected() {
+            let acc = accounts();
+            let mut channel = new_channel();
+            let preimage = [6u8; 32];
+            seed_htlc(&mut channel, &preimage, 30, 500);
+
+            set_balance(contract_id(), 100);
+            set_block_timestamp(500);
+            set_caller(acc.bob);
+
+            assert_eq!(channel.claim_htlc(preimage), Err(Error::HtlcExpired));
+            assert_eq!(channel.htlc_locked, 30);
+        }
+
+        #[ink::test]
+        fn refund_htlc_after_timeout_releases_reservation() {
+            let acc = accounts();
+            let mut channel = new_channel();
+            let preimage = [7u8; 32];
+            let payment_hash = seed_htlc(&mut channel, &preimage, 30, 500);
+
+            set_block_timestamp(600);
+            set_caller(acc.alice);
+
+            assert_eq!(channel.refund_htlc(payment_hash), Ok(()));
+            assert_eq!(channel.htlc_locked, 0);
+            assert!(channel.htlcs.get(payment_hash).is_none());
+        }
+
+        #[ink::test]
+        fn close_rejects_payout_beyond_free_balance() {
+            let acc = accounts();
+            let mut channel = new_channel();
+            let preimage = [8u8; 32];
+            seed_htlc(&mut channel, &preimage, 100, 500);
+
+            set_balance(contract_id(), 100);
+            set_caller(acc.bob);
+
+            // The whole balance is reserved for the HTLC, so no payout is free.
+            assert_eq!(
+                channel.close(10, 1, [0; 33], [0; 65], [0; 65]),
+                Err(Error::InsufficientChannelBalance)
+            );
+        }
+
+        #[ink::test]
+        fn finalize_close_rejected_while_htlc_pending() {
+            let mut channel = new_channel();
+            channel.challenge_expiration = Some(100);
+            channel.closing_amount = 50;
+            let preimage = [9u8; 32];
+            seed_htlc(&mut channel, &preimage, 10, 500);
+
+            set_block_timestamp(200);
+            assert_eq!(channel.finalize_close(), Err(Error::PendingHtlcs));
+        }
+
+        #[ink::test]
+        fn cooperative_close_rejected_while_htlc_pending() {
+            let mut channel = new_channel();
+            let preimage = [10u8; 32];
+            seed_htlc(&mut channel, &preimage, 10, 500);
+
+            assert_eq!(
+                channel.cooperative_close(0, 0, 1, [0; 65], [0; 65]),
+                Err(Error::PendingHtlcs)
+            );
+        }
     }
 }
 